@@ -0,0 +1,790 @@
+//! The on-demand glyph atlas: a growing MSDF texture packer with LRU
+//! eviction, so a [MsdfAtlas](crate::MsdfAtlas) only pays for the glyphs it
+//! actually draws instead of rasterizing an entire face up front.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy::math::URect;
+use owned_ttf_parser::{Face, GlyphId};
+use thiserror::Error;
+
+use crate::MsdfAtlasLoaderError;
+
+/// The initial side length, in texels, of a freshly created [GlyphAtlas] or
+/// [ColorGlyphAtlas].
+const INITIAL_ATLAS_SIZE: u32 = 256;
+
+/// Identifies a resident atlas entry, whether it came from a font face's
+/// glyph table or was inserted directly as a
+/// [custom glyph](crate::CustomGlyphId).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AtlasKey {
+    Glyph(GlyphId),
+    Custom(crate::CustomGlyphId),
+}
+
+/// Errors produced while resolving a glyph against a [GlyphAtlas] or
+/// [ColorGlyphAtlas].
+#[derive(Debug, Error)]
+pub enum GlyphAtlasError {
+    /// The atlas has no room for this glyph: either every resident glyph
+    /// is still in use this frame, so nothing can be evicted, or evicting
+    /// the single least-recently-used idle glyph did not free a large
+    /// enough span. The caller should grow the atlas and retry.
+    #[error("glyph atlas is full and eviction could not make room this frame")]
+    Full,
+    /// Rasterizing the glyph itself failed.
+    #[error(transparent)]
+    Rasterize(#[from] MsdfAtlasLoaderError),
+    /// This glyph already failed to rasterize on a previous call and is
+    /// cached as permanently unrasterizable (e.g. a glyph with no outline,
+    /// like the space character) — the caller already logged this once and
+    /// shouldn't retry or warn on it again every frame.
+    #[error("glyph previously failed to rasterize; not retrying")]
+    PreviouslyFailed,
+}
+
+/// A packed, rasterized glyph's location and metrics.
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphEntry {
+    /// The glyph's rectangle within the atlas texture, in texels.
+    pub rect: URect,
+    /// The glyph's plane bounds in em units, i.e. where the rect should be
+    /// placed relative to the glyph's origin when drawn.
+    pub plane_bounds: (f32, f32, f32, f32),
+    /// The frame counter value as of this glyph's most recent use, for LRU
+    /// eviction.
+    last_used: u64,
+}
+
+/// A packed color glyph's location and metrics; identical in shape to
+/// [GlyphEntry] since the mask and color atlases share the same packing and
+/// eviction bookkeeping, just a different pixel stride.
+pub type ColorGlyphEntry = GlyphEntry;
+
+/// A single shelf in the shelf packer: a horizontal strip of a fixed height
+/// that glyphs are packed into left-to-right.
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor_x: u32,
+    /// Spans reclaimed by [ShelfPacker::free] from evicted entries, tried
+    /// before extending `cursor_x` so eviction actually makes room instead
+    /// of only delaying a `grow`.
+    free: Vec<(u32, u32)>,
+}
+
+/// A simple shelf/guillotine rectangle packer for the atlas texture.
+struct ShelfPacker {
+    size: u32,
+    shelves: Vec<Shelf>,
+}
+
+impl ShelfPacker {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: vec![],
+        }
+    }
+
+    /// Attempts to pack a `width`x`height` rectangle, reusing a reclaimed
+    /// span on a shelf it fits, falling back to extending a shelf's cursor,
+    /// and opening a new shelf if none of the existing ones fit it.
+    fn try_allocate(&mut self, width: u32, height: u32) -> Option<URect> {
+        for shelf in &mut self.shelves {
+            if height > shelf.height {
+                continue;
+            }
+
+            if let Some(i) = shelf
+                .free
+                .iter()
+                .position(|(start, end)| end - start >= width)
+            {
+                let (start, end) = shelf.free.remove(i);
+                if end - start > width {
+                    shelf.free.push((start + width, end));
+                }
+                return Some(URect::new(start, shelf.y, start + width, shelf.y + height));
+            }
+
+            if shelf.cursor_x + width <= self.size {
+                let rect = URect::new(
+                    shelf.cursor_x,
+                    shelf.y,
+                    shelf.cursor_x + width,
+                    shelf.y + height,
+                );
+                shelf.cursor_x += width;
+                return Some(rect);
+            }
+        }
+
+        let y = self.shelves.iter().map(|s| s.y + s.height).max().unwrap_or(0);
+        if y + height > self.size || width > self.size {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height,
+            cursor_x: width,
+            free: vec![],
+        });
+
+        Some(URect::new(0, y, width, y + height))
+    }
+
+    /// Reclaims an evicted entry's rect so a later [Self::try_allocate] can
+    /// reuse its span on the same shelf.
+    fn free(&mut self, rect: URect) {
+        if let Some(shelf) = self.shelves.iter_mut().find(|shelf| shelf.y == rect.min.y) {
+            shelf.free.push((rect.min.x, rect.max.x));
+        }
+    }
+}
+
+/// The packing and LRU eviction bookkeeping shared by [GlyphAtlas] and
+/// [ColorGlyphAtlas]. The two only differ in how many bytes they store per
+/// texel and how they rasterize a glyph that isn't resident yet, so both
+/// wrap this and supply their own rasterizer.
+struct PackedAtlas {
+    packer: ShelfPacker,
+    entries: HashMap<AtlasKey, GlyphEntry>,
+    glyphs_in_use: HashSet<AtlasKey>,
+    /// Keys whose rasterizer has already failed once. A glyph with no
+    /// outline (the space character, most visibly) fails identically every
+    /// time it's requested, so this is cached rather than re-running the
+    /// rasterizer and re-warning on it every single frame.
+    failed: HashSet<AtlasKey>,
+    frame: u64,
+    /// Raw texture data, `size * size * bytes_per_texel` bytes, kept
+    /// CPU-side. `grow` reallocates it and re-rasterizes every resident
+    /// font glyph into it rather than copying the old pixels over.
+    pixels: Vec<u8>,
+    size: u32,
+    bytes_per_texel: u32,
+}
+
+impl PackedAtlas {
+    fn new(bytes_per_texel: u32) -> Self {
+        Self {
+            packer: ShelfPacker::new(INITIAL_ATLAS_SIZE),
+            entries: HashMap::new(),
+            glyphs_in_use: HashSet::new(),
+            failed: HashSet::new(),
+            frame: 0,
+            pixels: vec![0; (INITIAL_ATLAS_SIZE * INITIAL_ATLAS_SIZE * bytes_per_texel) as usize],
+            size: INITIAL_ATLAS_SIZE,
+            bytes_per_texel,
+        }
+    }
+
+    /// Marks `key` in-use for this frame, bumping its LRU timestamp if it's
+    /// already resident. Returns whether it was already resident.
+    fn touch(&mut self, key: AtlasKey) -> bool {
+        self.glyphs_in_use.insert(key);
+
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = self.frame;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `key`'s entry, rasterizing and packing it via `rasterize` if
+    /// this is the first time it's been requested. Marks `key` in-use for
+    /// the current frame either way.
+    ///
+    /// If `rasterize` fails, that failure is cached so a glyph with no
+    /// outline (the space character, most visibly) doesn't re-run
+    /// `rasterize` or produce a fresh [GlyphAtlasError::Rasterize] to log
+    /// every single frame — only the first failure is reported; every
+    /// later call for the same key returns [GlyphAtlasError::PreviouslyFailed]
+    /// instead, which callers can ignore without warning.
+    fn get_or_rasterize(
+        &mut self,
+        key: AtlasKey,
+        rasterize: impl FnOnce() -> Result<(Vec<u8>, u32, u32, (f32, f32, f32, f32)), MsdfAtlasLoaderError>,
+    ) -> Result<&GlyphEntry, GlyphAtlasError> {
+        if self.touch(key) {
+            return Ok(self.entries.get(&key).unwrap());
+        }
+
+        if self.failed.contains(&key) {
+            return Err(GlyphAtlasError::PreviouslyFailed);
+        }
+
+        match rasterize() {
+            Ok((pixels, width, height, plane_bounds)) => {
+                self.insert(key, &pixels, width, height, plane_bounds)
+            }
+            Err(err) => {
+                self.failed.insert(key);
+                Err(GlyphAtlasError::Rasterize(err))
+            }
+        }
+    }
+
+    fn insert(
+        &mut self,
+        key: AtlasKey,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        plane_bounds: (f32, f32, f32, f32),
+    ) -> Result<&GlyphEntry, GlyphAtlasError> {
+        let rect = self.allocate(width, height)?;
+        self.blit(&rect, pixels, width);
+
+        self.entries.insert(
+            key,
+            GlyphEntry {
+                rect,
+                plane_bounds,
+                last_used: self.frame,
+            },
+        );
+
+        Ok(self.entries.get(&key).unwrap())
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Result<URect, GlyphAtlasError> {
+        if let Some(rect) = self.packer.try_allocate(width, height) {
+            return Ok(rect);
+        }
+
+        let lru = self
+            .entries
+            .iter()
+            .filter(|(key, _)| !self.glyphs_in_use.contains(key))
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, entry)| (*key, entry.rect));
+
+        let Some((lru, rect)) = lru else {
+            return Err(GlyphAtlasError::Full);
+        };
+
+        self.entries.remove(&lru);
+        self.packer.free(rect);
+
+        self.packer
+            .try_allocate(width, height)
+            .ok_or(GlyphAtlasError::Full)
+    }
+
+    fn blit(&mut self, rect: &URect, pixels: &[u8], src_width: u32) {
+        let stride = self.bytes_per_texel as usize;
+        for row in 0..rect.height() {
+            let dst_start = (((rect.min.y + row) * self.size + rect.min.x) as usize) * stride;
+            let src_start = (row * src_width) as usize * stride;
+            let width = rect.width() as usize * stride;
+            self.pixels[dst_start..dst_start + width]
+                .copy_from_slice(&pixels[src_start..src_start + width]);
+        }
+    }
+
+    /// Marks the end of the frame: clears the in-use set so idle glyphs
+    /// become eligible for eviction again, and advances the LRU clock.
+    fn trim(&mut self) {
+        self.glyphs_in_use.clear();
+        self.frame += 1;
+    }
+
+    /// Doubles the texture size, re-packing every glyph that's still
+    /// resident. Font-backed glyphs are re-rasterized via `rasterize`;
+    /// custom glyphs are dropped and must be re-inserted by the caller,
+    /// since this atlas has no way to re-derive their pixels on its own.
+    fn grow(
+        &mut self,
+        mut rasterize: impl FnMut(
+            GlyphId,
+        ) -> Result<(Vec<u8>, u32, u32, (f32, f32, f32, f32)), MsdfAtlasLoaderError>,
+    ) {
+        self.size *= 2;
+        self.pixels = vec![0; (self.size * self.size * self.bytes_per_texel) as usize];
+        self.packer = ShelfPacker::new(self.size);
+
+        let live: Vec<AtlasKey> = self.entries.keys().copied().collect();
+        self.entries.clear();
+
+        for key in live {
+            let AtlasKey::Glyph(glyph) = key else {
+                continue;
+            };
+
+            if let Ok((pixels, width, height, plane_bounds)) = rasterize(glyph) {
+                if let Some(rect) = self.packer.try_allocate(width, height) {
+                    self.blit(&rect, &pixels, width);
+                    self.entries.insert(
+                        key,
+                        GlyphEntry {
+                            rect,
+                            plane_bounds,
+                            last_used: self.frame,
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// A lazily-populated, growable MSDF glyph atlas with LRU eviction.
+///
+/// Glyphs are rasterized the first time they're requested via
+/// [Self::get_or_rasterize] rather than all at once when the face is loaded.
+/// Callers must mark every glyph they draw each frame (which
+/// [Self::get_or_rasterize] does implicitly) and call [Self::trim] once per
+/// frame afterwards so eviction only considers glyphs that are truly idle.
+pub struct GlyphAtlas {
+    storage: PackedAtlas,
+}
+
+impl GlyphAtlas {
+    /// Creates an empty atlas at the default initial size.
+    pub fn new() -> Self {
+        Self {
+            // `generate_msdf` produces a true 3-channel (RGB) MSDF, decoded
+            // in msdf.wgsl via the median of all three channels — a real
+            // single-channel distance field would need `generate_sdf`
+            // instead, decoded without a median.
+            storage: PackedAtlas::new(3),
+        }
+    }
+
+    /// The current texture side length, in texels.
+    pub fn size(&self) -> u32 {
+        self.storage.size
+    }
+
+    /// Raw 3-channel (RGB) MSDF texture data, `size() * size() * 3` bytes.
+    pub fn pixels(&self) -> &[u8] {
+        &self.storage.pixels
+    }
+
+    /// Returns the entry for `glyph`, rasterizing and packing it if this is
+    /// the first time it's been requested. Marks the glyph in-use for the
+    /// current frame either way.
+    ///
+    /// On [GlyphAtlasError::Full], the caller should [Self::grow] the atlas
+    /// and call this again. Eviction never touches in-use entries, but
+    /// [Self::grow] re-packs every font glyph at a new rect and drops custom
+    /// glyphs, so entries returned earlier this frame are stale after it. On
+    /// [GlyphAtlasError::PreviouslyFailed], the caller should treat it the
+    /// same as a fresh [GlyphAtlasError::Rasterize] except without logging
+    /// it again — a glyph with no outline (e.g. the space character) would
+    /// otherwise fail, and get warned about, on every single frame.
+    pub fn get_or_rasterize(
+        &mut self,
+        face: &Face,
+        glyph: GlyphId,
+    ) -> Result<&GlyphEntry, GlyphAtlasError> {
+        let key = AtlasKey::Glyph(glyph);
+        self.storage.get_or_rasterize(key, || rasterize_glyph(face, glyph))
+    }
+
+    /// Inserts an already-rasterized 3-channel (RGB) MSDF (e.g. from a
+    /// [CustomGlyphSource::Msdf](crate::CustomGlyphSource::Msdf)) under
+    /// `id`, bypassing font rasterization entirely.
+    pub fn insert_custom(
+        &mut self,
+        id: crate::CustomGlyphId,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        plane_bounds: (f32, f32, f32, f32),
+    ) -> Result<&GlyphEntry, GlyphAtlasError> {
+        let key = AtlasKey::Custom(id);
+
+        if self.storage.touch(key) {
+            return Ok(self.storage.entries.get(&key).unwrap());
+        }
+
+        self.storage.insert(key, pixels, width, height, plane_bounds)
+    }
+
+    /// Marks the end of the frame: clears the in-use set so idle glyphs
+    /// become eligible for eviction again, and advances the LRU clock.
+    pub fn trim(&mut self) {
+        self.storage.trim();
+    }
+
+    /// Doubles the atlas texture size, re-packing every glyph that's still
+    /// resident. Font-backed glyphs are re-rasterized from `face`; custom
+    /// glyphs are dropped and must be re-inserted by the caller, since this
+    /// atlas has no way to re-derive their pixels on its own.
+    pub fn grow(&mut self, face: &Face) {
+        self.storage.grow(|glyph| rasterize_glyph(face, glyph));
+    }
+}
+
+impl Default for GlyphAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rasterizes a single glyph's MSDF, returning its pixels (row-major,
+/// 3 channels per texel), dimensions, and plane bounds in em units.
+fn rasterize_glyph(
+    face: &Face,
+    glyph: GlyphId,
+) -> Result<(Vec<u8>, u32, u32, (f32, f32, f32, f32)), MsdfAtlasLoaderError> {
+    use owned_ttf_parser::OutlineBuilder;
+
+    struct Outliner {
+        shape: msdfgen::Shape,
+        contour: Option<msdfgen::Contour>,
+    }
+
+    impl OutlineBuilder for Outliner {
+        fn move_to(&mut self, x: f32, y: f32) {
+            if let Some(contour) = self.contour.take() {
+                self.shape.add_contour(contour);
+            }
+            let mut contour = msdfgen::Contour::new();
+            contour.set_start(msdfgen::Point2::new(x as f64, y as f64));
+            self.contour = Some(contour);
+        }
+
+        fn line_to(&mut self, x: f32, y: f32) {
+            if let Some(contour) = &mut self.contour {
+                contour.add_edge(msdfgen::EdgeSegment::new_linear(msdfgen::Point2::new(
+                    x as f64, y as f64,
+                )));
+            }
+        }
+
+        fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+            if let Some(contour) = &mut self.contour {
+                contour.add_edge(msdfgen::EdgeSegment::new_quadratic(
+                    msdfgen::Point2::new(x1 as f64, y1 as f64),
+                    msdfgen::Point2::new(x as f64, y as f64),
+                ));
+            }
+        }
+
+        fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+            if let Some(contour) = &mut self.contour {
+                contour.add_edge(msdfgen::EdgeSegment::new_cubic(
+                    msdfgen::Point2::new(x1 as f64, y1 as f64),
+                    msdfgen::Point2::new(x2 as f64, y2 as f64),
+                    msdfgen::Point2::new(x as f64, y as f64),
+                ));
+            }
+        }
+
+        fn close(&mut self) {
+            if let Some(contour) = self.contour.take() {
+                self.shape.add_contour(contour);
+            }
+        }
+    }
+
+    let mut outliner = Outliner {
+        shape: msdfgen::Shape::new(),
+        contour: None,
+    };
+
+    let bbox = face
+        .outline_glyph(glyph, &mut outliner)
+        .ok_or(MsdfAtlasLoaderError::GlyphShape(glyph))?;
+
+    if let Some(contour) = outliner.contour.take() {
+        outliner.shape.add_contour(contour);
+    }
+
+    let units_per_em = face.units_per_em() as f32;
+    let plane_bounds = (
+        bbox.x_min as f32 / units_per_em,
+        bbox.y_min as f32 / units_per_em,
+        bbox.x_max as f32 / units_per_em,
+        bbox.y_max as f32 / units_per_em,
+    );
+
+    const GLYPH_PADDING: u32 = 4;
+    let width = (bbox.width() as u32).max(1) + GLYPH_PADDING * 2;
+    let height = (bbox.height() as u32).max(1) + GLYPH_PADDING * 2;
+
+    let range = msdfgen::Range::Px(4.0);
+    let bitmap = outliner
+        .shape
+        .generate_msdf(width, height, range)
+        .ok_or(MsdfAtlasLoaderError::AutoFraming {
+            glyph,
+            width: width as usize,
+            height: height as usize,
+            range,
+        })?;
+
+    Ok((bitmap, width, height, plane_bounds))
+}
+
+/// A lazily-populated, growable RGBA atlas for color glyphs (COLR/CPAL
+/// layered glyphs, and embedded `sbix`/`CBDT` bitmaps), packed and evicted
+/// the same way as [GlyphAtlas] but storing 4 bytes per texel instead of 3.
+pub struct ColorGlyphAtlas {
+    storage: PackedAtlas,
+}
+
+impl ColorGlyphAtlas {
+    pub fn new() -> Self {
+        Self {
+            storage: PackedAtlas::new(4),
+        }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.storage.size
+    }
+
+    /// Raw RGBA texture data, `size() * size() * 4` bytes.
+    pub fn pixels(&self) -> &[u8] {
+        &self.storage.pixels
+    }
+
+    /// Returns the entry for `glyph`, rasterizing it to RGBA if this is the
+    /// first time it's been requested. See [GlyphAtlas::get_or_rasterize]
+    /// for the eviction, retry, and previously-failed contract.
+    pub fn get_or_rasterize(
+        &mut self,
+        face: &Face,
+        glyph: GlyphId,
+    ) -> Result<&ColorGlyphEntry, GlyphAtlasError> {
+        let key = AtlasKey::Glyph(glyph);
+        self.storage.get_or_rasterize(key, || rasterize_color_glyph(face, glyph))
+    }
+
+    /// Inserts an already-rasterized RGBA region (e.g. from a
+    /// [CustomGlyphSource::Color](crate::CustomGlyphSource::Color)) under
+    /// `id`, bypassing font rasterization entirely.
+    pub fn insert_custom(
+        &mut self,
+        id: crate::CustomGlyphId,
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        plane_bounds: (f32, f32, f32, f32),
+    ) -> Result<&ColorGlyphEntry, GlyphAtlasError> {
+        let key = AtlasKey::Custom(id);
+
+        if self.storage.touch(key) {
+            return Ok(self.storage.entries.get(&key).unwrap());
+        }
+
+        self.storage.insert(key, pixels, width, height, plane_bounds)
+    }
+
+    pub fn trim(&mut self) {
+        self.storage.trim();
+    }
+
+    /// Doubles the atlas texture size, re-packing every glyph that's still
+    /// resident. Font-backed glyphs are re-rasterized from `face`; custom
+    /// glyphs are dropped and must be re-inserted by the caller.
+    pub fn grow(&mut self, face: &Face) {
+        self.storage.grow(|glyph| rasterize_color_glyph(face, glyph));
+    }
+}
+
+impl Default for ColorGlyphAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Rasterizes a color glyph to RGBA, pulling from an embedded bitmap
+/// (`sbix`/`CBDT`) if present.
+///
+/// COLR/CPAL layer compositing isn't implemented yet: such glyphs currently
+/// rasterize as fully transparent until layer flattening is added.
+fn rasterize_color_glyph(
+    face: &Face,
+    glyph: GlyphId,
+) -> Result<(Vec<u8>, u32, u32, (f32, f32, f32, f32)), MsdfAtlasLoaderError> {
+    if let Some(image) = face.glyph_raster_image(glyph, u16::MAX) {
+        return match image.format {
+            // Real-world sbix/CBDT emoji fonts (Apple Color Emoji, Noto
+            // Color Emoji) store this data PNG-encoded, not as raw RGBA8 —
+            // decode it rather than feeding compressed bytes straight into
+            // `chunks_exact`, which would produce a buffer far shorter than
+            // `width * height * 4` and panic in `PackedAtlas::blit`.
+            owned_ttf_parser::RasterImageFormat::PNG => {
+                let decoded = image::load_from_memory(image.data)
+                    .map_err(|_| MsdfAtlasLoaderError::GlyphShape(glyph))?
+                    .to_rgba8();
+
+                // The decoded PNG's own dimensions are authoritative; the
+                // raster table's declared width/height are only hints.
+                let (width, height) = decoded.dimensions();
+
+                // Embedded bitmaps are authored at their own pixels_per_em,
+                // unrelated to the face's glyf-outline units_per_em, and
+                // carry their own bearing (x, y) — both have to come from
+                // the raster image itself, not the outline's metrics.
+                let scale = image.pixels_per_em as f32;
+                let plane_bounds = (
+                    image.x as f32 / scale,
+                    image.y as f32 / scale,
+                    (image.x as f32 + width as f32) / scale,
+                    (image.y as f32 + height as f32) / scale,
+                );
+
+                Ok((decoded.into_raw(), width, height, plane_bounds))
+            }
+            // Other raster formats aren't implemented yet; reject rather
+            // than silently reinterpreting unknown bytes as RGBA8.
+            _ => Err(MsdfAtlasLoaderError::GlyphShape(glyph)),
+        };
+    }
+
+    // TODO flatten COLR/CPAL layers into a composited RGBA bitmap.
+    let size = 1;
+    Ok((vec![0; size * size * 4], size as u32, size as u32, (0.0, 0.0, 0.0, 0.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels(width: u32, height: u32) -> Vec<u8> {
+        vec![0u8; (width * height) as usize]
+    }
+
+    #[test]
+    fn shelf_packer_packs_left_to_right_then_wraps() {
+        let mut packer = ShelfPacker::new(8);
+        let a = packer.try_allocate(4, 2).unwrap();
+        let b = packer.try_allocate(4, 2).unwrap();
+        assert_eq!(a, URect::new(0, 0, 4, 2));
+        assert_eq!(b, URect::new(4, 0, 8, 2));
+
+        // The shelf is full width-wise, so another same-height rect opens a
+        // new shelf above it rather than failing.
+        let c = packer.try_allocate(4, 2).unwrap();
+        assert_eq!(c, URect::new(0, 2, 4, 4));
+    }
+
+    #[test]
+    fn shelf_packer_rejects_rect_wider_than_the_atlas() {
+        let mut packer = ShelfPacker::new(8);
+        assert!(packer.try_allocate(9, 2).is_none());
+    }
+
+    #[test]
+    fn shelf_packer_reports_none_when_exhausted() {
+        let mut packer = ShelfPacker::new(4);
+        assert!(packer.try_allocate(4, 4).is_some());
+        assert!(packer.try_allocate(1, 1).is_none());
+    }
+
+    #[test]
+    fn shelf_packer_reuses_a_freed_span() {
+        let mut packer = ShelfPacker::new(4);
+        let a = packer.try_allocate(2, 4).unwrap();
+        packer.try_allocate(2, 4).unwrap();
+        assert!(packer.try_allocate(1, 1).is_none());
+
+        // Without `free`, this reclaimed span would stay unreachable and
+        // every future allocation here would report the atlas full.
+        packer.free(a);
+        let reused = packer.try_allocate(2, 4).unwrap();
+        assert_eq!(reused, a);
+    }
+
+    #[test]
+    fn packed_atlas_evicts_the_least_recently_used_entry() {
+        let mut atlas = PackedAtlas::new(1);
+        atlas.size = 4;
+        atlas.pixels = vec![0; 16];
+        atlas.packer = ShelfPacker::new(4);
+
+        let key_a = AtlasKey::Glyph(GlyphId(1));
+        let key_b = AtlasKey::Glyph(GlyphId(2));
+        let key_c = AtlasKey::Glyph(GlyphId(3));
+
+        atlas.touch(key_a);
+        atlas
+            .insert(key_a, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0))
+            .unwrap();
+        atlas.trim();
+
+        atlas.touch(key_b);
+        atlas
+            .insert(key_b, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0))
+            .unwrap();
+        atlas.trim();
+
+        // The atlas (a 4x4 texture packed with two 4x2 entries) is now
+        // full. `key_a` hasn't been touched since frame 0 and `key_b` isn't
+        // in use this frame, so inserting a third glyph must evict `key_a`
+        // and actually reclaim its packed space rather than reporting Full.
+        atlas.touch(key_c);
+        let result = atlas.insert(key_c, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0));
+
+        assert!(
+            result.is_ok(),
+            "eviction should reclaim packer space for a new glyph"
+        );
+        assert!(!atlas.entries.contains_key(&key_a), "LRU entry should be evicted");
+        assert!(
+            atlas.entries.contains_key(&key_b),
+            "more recently used entry should survive"
+        );
+    }
+
+    #[test]
+    fn packed_atlas_full_when_nothing_is_evictable() {
+        let mut atlas = PackedAtlas::new(1);
+        atlas.size = 4;
+        atlas.pixels = vec![0; 16];
+        atlas.packer = ShelfPacker::new(4);
+
+        let key_a = AtlasKey::Glyph(GlyphId(1));
+        let key_b = AtlasKey::Glyph(GlyphId(2));
+        let key_c = AtlasKey::Glyph(GlyphId(3));
+
+        atlas.touch(key_a);
+        atlas
+            .insert(key_a, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0))
+            .unwrap();
+        atlas.touch(key_b);
+        atlas
+            .insert(key_b, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0))
+            .unwrap();
+
+        // No `trim` happened, so both entries are still marked in-use this
+        // frame and neither is eligible for eviction.
+        atlas.touch(key_c);
+        let result = atlas.insert(key_c, &pixels(4, 2), 4, 2, (0.0, 0.0, 0.0, 0.0));
+
+        assert!(matches!(result, Err(GlyphAtlasError::Full)));
+    }
+
+    #[test]
+    fn packed_atlas_caches_a_rasterize_failure() {
+        let mut atlas = PackedAtlas::new(1);
+        let key = AtlasKey::Glyph(GlyphId(1));
+        let mut calls = 0;
+
+        let rasterize = |calls: &mut u32| {
+            *calls += 1;
+            Err(MsdfAtlasLoaderError::GlyphShape(GlyphId(1)))
+        };
+
+        let first = atlas.get_or_rasterize(key, || rasterize(&mut calls));
+        assert!(matches!(first, Err(GlyphAtlasError::Rasterize(_))));
+        assert_eq!(calls, 1);
+
+        // A glyph with no outline (e.g. the space character) fails
+        // identically every time; once cached, later calls must not
+        // re-invoke the rasterizer or surface a fresh Rasterize error to
+        // warn about every frame.
+        let second = atlas.get_or_rasterize(key, || rasterize(&mut calls));
+        assert!(matches!(second, Err(GlyphAtlasError::PreviouslyFailed)));
+        assert_eq!(calls, 1, "a cached failure must not re-run the rasterizer");
+    }
+}
@@ -1,6 +1,5 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
-use atlas::GlyphAtlas;
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
     prelude::*,
@@ -35,30 +34,42 @@ pub enum MsdfAtlasLoaderError {
     },
 }
 
+/// Settings for [MsdfAtlasLoader], letting a `.ttc`/`.otc` collection or a
+/// multi-face font pick which face to load.
+#[derive(Debug, Clone, Copy)]
+pub struct MsdfAtlasLoaderSettings {
+    /// The index of the face to load out of the font file's collection.
+    /// Ignored (and assumed `0`) for single-face font files.
+    pub face_index: u32,
+}
+
+impl Default for MsdfAtlasLoaderSettings {
+    fn default() -> Self {
+        Self { face_index: 0 }
+    }
+}
+
 #[derive(Default)]
 pub struct MsdfAtlasLoader;
 
 impl AssetLoader for MsdfAtlasLoader {
     type Asset = MsdfAtlas;
-    type Settings = ();
+    type Settings = MsdfAtlasLoaderSettings;
     type Error = MsdfAtlasLoaderError;
 
     fn load<'a>(
         &'a self,
         reader: &'a mut Reader,
-        _settings: &'a (),
+        settings: &'a MsdfAtlasLoaderSettings,
         _load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<MsdfAtlas, Self::Error>> {
         Box::pin(async move {
             let mut bytes = Vec::new();
             reader.read_to_end(&mut bytes).await?;
-            // TODO support non-zero face indices
-            let face = OwnedFace::from_vec(bytes, 0)?;
-            let (atlas, _glyph_errors) = GlyphAtlas::new(face.as_face_ref())?;
+            let face = OwnedFace::from_vec(bytes, settings.face_index)?;
 
             Ok(MsdfAtlas {
                 face: Arc::new(face),
-                atlas: Arc::new(atlas),
             })
         })
     }
@@ -67,7 +78,6 @@ impl AssetLoader for MsdfAtlasLoader {
 #[derive(Asset, Clone, TypePath)]
 pub struct MsdfAtlas {
     pub face: Arc<OwnedFace>,
-    pub atlas: Arc<GlyphAtlas>,
 }
 
 /// A bundle of the components necessary to draw a plane of MSDF glyphs.
@@ -107,29 +117,258 @@ pub struct MsdfGlow {
     pub offset: Vec2,
 }
 
+/// The visual pattern a [DecorationLine] is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecorationStyle {
+    #[default]
+    Solid,
+    Dotted,
+    Dashed,
+    Double,
+    /// A sine-offset undercurl.
+    Wavy,
+}
+
+/// A single underline/overline/strikethrough rule drawn across a laid-out
+/// line's extent.
+#[derive(Debug, Clone, Copy)]
+pub struct DecorationLine {
+    /// The visual pattern to draw the rule with.
+    pub style: DecorationStyle,
+
+    /// The rule's color.
+    pub color: Color,
+
+    /// The rule's thickness in layout units. `None` defaults to the font's
+    /// underline metrics (for underlines) or strikeout metrics (for
+    /// strikethroughs), scaled into layout units.
+    pub thickness: Option<f32>,
+
+    /// The rule's vertical offset from the baseline, in layout units.
+    /// `None` defaults to the font's corresponding metric.
+    pub position: Option<f32>,
+}
+
+impl Default for DecorationLine {
+    fn default() -> Self {
+        Self {
+            style: DecorationStyle::default(),
+            color: Color::WHITE,
+            thickness: None,
+            position: None,
+        }
+    }
+}
+
+/// Applies underline, overline, and/or strikethrough rules to an [MsdfDraw]
+/// or [MsdfText], spanning the extent of each laid-out line.
+#[derive(Component, Default)]
+pub struct MsdfDecoration {
+    pub underline: Option<DecorationLine>,
+    pub overline: Option<DecorationLine>,
+    pub strikethrough: Option<DecorationLine>,
+}
+
+/// The horizontal extent and baseline of a single laid-out line, recorded
+/// by [layout] alongside [MsdfDraw] so [MsdfDecoration] rules know where to
+/// draw.
+#[derive(Debug, Clone, Copy)]
+pub struct MsdfLineExtent {
+    /// The line's baseline, in the same space as [MsdfGlyph::pos].
+    pub y: f32,
+    pub start_x: f32,
+    pub end_x: f32,
+}
+
+/// The per-line geometry produced by [layout] for an [MsdfText], consumed
+/// by [MsdfDecoration] rendering.
+#[derive(Component, Default)]
+pub struct MsdfLines(pub Vec<MsdfLineExtent>);
+
+/// An ordered list of fallback [MsdfAtlas]es consulted by [layout] when the
+/// primary [MsdfText::atlas] has no glyph for a character, so mixed-script
+/// strings (e.g. Latin + CJK + symbols) render instead of dropping glyphs.
+#[derive(Component, Clone, Default)]
+pub struct MsdfFontStack(pub Vec<Handle<MsdfAtlas>>);
+
+/// Identifies an entry in a [CustomGlyphRegistry].
+pub type CustomGlyphId = u32;
+
+/// A user-supplied glyph spliced into an [MsdfText] run, advanced and
+/// positioned by the same layout pass that shapes real text.
+#[derive(Debug, Clone, Copy)]
+pub struct CustomGlyph {
+    /// Identifies which [CustomGlyphRegistry] entry to draw.
+    pub id: CustomGlyphId,
+
+    /// The glyph's footprint in the same layout units as font glyph
+    /// advances, used both for the quad's size and its horizontal advance.
+    pub size: Vec2,
+
+    /// An offset applied after layout, e.g. to align an icon's baseline
+    /// with the surrounding text.
+    pub offset: Vec2,
+
+    /// Whether to round the glyph's placement to the nearest physical
+    /// pixel, to avoid blurry icons at small sizes.
+    pub snap_to_physical: bool,
+}
+
+/// A single piece of an [MsdfText] run: either a span of real text to shape
+/// against the font, or a [CustomGlyph] placeholder.
+#[derive(Debug, Clone)]
+pub enum MsdfTextItem {
+    /// A span of text to shape normally.
+    Text(String),
+    /// An inline icon/custom glyph.
+    Custom(CustomGlyph),
+}
+
+impl From<&str> for MsdfTextItem {
+    fn from(text: &str) -> Self {
+        MsdfTextItem::Text(text.to_owned())
+    }
+}
+
+impl From<String> for MsdfTextItem {
+    fn from(text: String) -> Self {
+        MsdfTextItem::Text(text)
+    }
+}
+
+impl From<CustomGlyph> for MsdfTextItem {
+    fn from(custom: CustomGlyph) -> Self {
+        MsdfTextItem::Custom(custom)
+    }
+}
+
+/// The pre-rasterized pixels backing a [CustomGlyphId], uploaded into
+/// whichever atlas matches its content type the first time it's drawn.
+pub enum CustomGlyphSource {
+    /// An RGBA region uploaded into the color atlas.
+    Color {
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+    /// A 3-channel (RGB) MSDF uploaded into the mask atlas.
+    Msdf {
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+    },
+}
+
+/// Maps [CustomGlyphId]s to their source pixels. Register entries here
+/// before referencing them from an [MsdfText]'s [CustomGlyph] items.
+#[derive(Resource, Default)]
+pub struct CustomGlyphRegistry(HashMap<CustomGlyphId, CustomGlyphSource>);
+
+impl CustomGlyphRegistry {
+    /// Registers `source` under `id`, overwriting any previous entry.
+    pub fn insert(&mut self, id: CustomGlyphId, source: CustomGlyphSource) {
+        self.0.insert(id, source);
+    }
+
+    /// Returns the source registered for `id`, if any.
+    pub fn get(&self, id: CustomGlyphId) -> Option<&CustomGlyphSource> {
+        self.0.get(&id)
+    }
+}
+
+/// Horizontal alignment of an [MsdfText]'s laid-out lines relative to its
+/// anchor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HorizontalAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// Vertical alignment of an [MsdfText]'s block of lines relative to its
+/// anchor.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VerticalAlign {
+    Top,
+    #[default]
+    Middle,
+    Bottom,
+}
+
 /// A component that shapes and draws text using [MsdfDraw].
 #[derive(Component)]
 pub struct MsdfText {
     /// The [MsdfAtlas] to use for this text.
     pub atlas: Handle<MsdfAtlas>,
 
-    /// The text to render.
-    pub content: String,
+    /// The sequence of text spans and inline [CustomGlyph]s to render.
+    pub content: Vec<MsdfTextItem>,
 
     /// The text's color.
     pub color: Color,
+
+    /// The maximum width a line may reach before greedily wrapping at the
+    /// next whitespace. `None` disables wrapping, so lines only break on
+    /// explicit `\n`.
+    pub wrap_width: Option<f32>,
+
+    /// The distance between the baselines of consecutive lines, in the
+    /// font's design units scaled the same way as glyph advances.
+    pub line_height: f32,
+
+    /// How lines are aligned horizontally relative to the anchor.
+    pub h_align: HorizontalAlign,
+
+    /// How the whole block of lines is aligned vertically relative to the
+    /// anchor.
+    pub v_align: VerticalAlign,
+}
+
+impl Default for MsdfText {
+    fn default() -> Self {
+        Self {
+            atlas: Handle::default(),
+            content: vec![],
+            color: Color::WHITE,
+            wrap_width: None,
+            line_height: 1.0,
+            h_align: HorizontalAlign::default(),
+            v_align: VerticalAlign::default(),
+        }
+    }
 }
 
 /// A component that draws a list of atlas glyphs onto a plane.
 #[derive(Component)]
 pub struct MsdfDraw {
-    /// The [MsdfAtlas] to use for this draw.
-    pub atlas: Handle<MsdfAtlas>,
+    /// The atlases referenced by this draw. Index `0` is always the
+    /// [MsdfText::atlas] this draw was shaped from; further entries are
+    /// every loaded [MsdfFontStack] fallback, in stack order, whether or
+    /// not shaping actually used a glyph from it. [MsdfGlyph::atlas_index]
+    /// indexes into this list.
+    pub atlases: Vec<Handle<MsdfAtlas>>,
 
     /// The list of glyphs to draw.
     pub glyphs: Vec<MsdfGlyph>,
 }
 
+/// Which atlas texture, and sampling behavior, a [MsdfGlyph] should draw
+/// from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphContent {
+    /// A 3-channel (RGB) MSDF mask, tinted by [MsdfGlyph::color].
+    #[default]
+    Mask,
+    /// A pre-rendered RGBA region (COLR/CPAL, `sbix`, or `CBDT` glyphs).
+    /// [MsdfGlyph::color] is ignored for these.
+    Color,
+    /// A [CustomGlyph], drawn from its [CustomGlyphRegistry] entry instead
+    /// of the font's own atlas. [MsdfGlyph::color] only applies if that
+    /// entry is an MSDF mask.
+    Custom(CustomGlyphId),
+}
+
 /// A single instance of a MSDF glyph.
 pub struct MsdfGlyph {
     /// The position of this glyph's anchor.
@@ -138,14 +377,260 @@ pub struct MsdfGlyph {
     /// The color to draw this glyph.
     pub color: Color,
 
-    /// The index of this glyph within the [MsdfAtlas].
+    /// The index of this glyph within the [MsdfAtlas]. Meaningless when
+    /// [Self::content] is [GlyphContent::Custom].
     pub index: u16,
+
+    /// Whether this glyph samples the mask atlas or the color atlas.
+    pub content: GlyphContent,
+
+    /// Indexes into [MsdfDraw::atlases] for the face this glyph was shaped
+    /// from. Meaningless when [Self::content] is [GlyphContent::Custom].
+    pub atlas_index: usize,
+}
+
+/// Returns `glyph`'s horizontal advance in em units, i.e. scaled down by the
+/// face's `units_per_em` so it composes directly with layout-space
+/// coordinates.
+fn glyph_advance(face: &owned_ttf_parser::Face, glyph: GlyphId) -> f32 {
+    let units_per_em = face.units_per_em() as f32;
+    let advance = face.glyph_hor_advance(glyph).unwrap_or(0) as f32;
+    advance / units_per_em
+}
+
+/// Returns the pairwise kerning adjustment between `left` and `right` in em
+/// units, checking the `kern` table's horizontal subtables.
+///
+/// GPOS pair adjustment isn't exposed by `owned_ttf_parser`, so only `kern`
+/// is consulted; most Latin-script fonts still ship `kern` alongside GPOS.
+fn glyph_kerning(face: &owned_ttf_parser::Face, left: GlyphId, right: GlyphId) -> f32 {
+    let Some(kern) = face.tables().kern else {
+        return 0.0;
+    };
+
+    let units_per_em = face.units_per_em() as f32;
+
+    let adjustment = kern
+        .subtables
+        .into_iter()
+        .filter(|subtable| subtable.horizontal && !subtable.variable)
+        .find_map(|subtable| subtable.glyphs_kerning(left, right))
+        .unwrap_or(0);
+
+    adjustment as f32 / units_per_em
+}
+
+/// Determines whether `glyph` should be sourced from the mask atlas or the
+/// color atlas, i.e. whether the face has color data (COLR/CPAL layers, or
+/// an embedded `sbix`/`CBDT` bitmap) for it.
+///
+/// COLR/CPAL layer compositing isn't implemented yet (see
+/// `atlas::rasterize_color_glyph`), so a glyph is only routed to the color
+/// atlas when it has an embedded raster bitmap; other color glyphs fall
+/// back to drawing their outline through the mask atlas, which is closer to
+/// correct than the fully transparent glyph a blind `Color` route would
+/// produce.
+fn glyph_content(face: &owned_ttf_parser::Face, glyph: GlyphId) -> GlyphContent {
+    if face.is_color_glyph(glyph) {
+        if face.glyph_raster_image(glyph, u16::MAX).is_some() {
+            return GlyphContent::Color;
+        }
+
+        warn!("glyph {glyph:?} has COLR/CPAL layers with no raster fallback; drawing its outline instead");
+    }
+
+    GlyphContent::Mask
+}
+
+/// What a [ShapedGlyph] draws: a real font glyph (and which face of the
+/// [MsdfFontStack] it came from), or an inline [CustomGlyph].
+enum ShapedContent {
+    Glyph { glyph: GlyphId, face_index: usize },
+    Custom(CustomGlyph),
+}
+
+/// Finds the first face in `faces` (primary face first, then fallbacks in
+/// order) that has a glyph for `c`, returning its index into `faces` and
+/// the glyph.
+fn resolve_glyph(faces: &[&owned_ttf_parser::Face], c: char) -> Option<(usize, GlyphId)> {
+    faces
+        .iter()
+        .enumerate()
+        .find_map(|(index, face)| face.glyph_index(c).map(|glyph| (index, glyph)))
+}
+
+/// A single shaped glyph within a laid-out line, before alignment offsets
+/// have been applied.
+struct ShapedGlyph {
+    content: ShapedContent,
+    /// Horizontal offset from the start of the line.
+    x: f32,
+}
+
+/// The greedy wrap decision used by `wrap_if_needed!` in [shape_lines]:
+/// given the position a glyph would advance the cursor to and the last
+/// recorded break point, decides whether the line should be split there and
+/// returns its `(break_at, break_width)`, or `None` if no wrap is needed (or
+/// possible, for a run with no breakable boundary yet).
+///
+/// Pulled out as a pure function, independent of font/glyph concerns, so
+/// the wrap condition can be covered directly by a `#[test]`; the rebase
+/// arithmetic that follows a wrap lives in [split_and_rebase].
+fn wrap_decision(
+    advance_end: f32,
+    wrap_width: Option<f32>,
+    last_break: Option<(usize, f32)>,
+) -> Option<(usize, f32)> {
+    let max_width = wrap_width?;
+    if advance_end <= max_width {
+        return None;
+    }
+    last_break
+}
+
+/// Splits `line` at `break_at`, returning the carried-over remainder with
+/// its glyphs' `x` rebased by `break_width`.
+///
+/// The remainder's glyphs still carry `x` from the frame *before* this
+/// split; without rebasing them here they'd stay in that old frame while
+/// every glyph appended after the split is already computed from the
+/// already-decremented `cursor` (the new frame), corrupting the positions
+/// of every line wrapped past its first word. Pulled out as its own
+/// function — independent of font/glyph concerns beyond `ShapedGlyph::x` —
+/// so that arithmetic can be covered directly by a `#[test]`.
+fn split_and_rebase(line: &mut Vec<ShapedGlyph>, break_at: usize, break_width: f32) -> Vec<ShapedGlyph> {
+    let mut rest = line.split_off(break_at);
+    for glyph in &mut rest {
+        glyph.x -= break_width;
+    }
+    rest
+}
+
+/// Greedily shapes `items` against `face`, breaking into lines on `\n` and,
+/// if `wrap_width` is set, wrapping at the last whitespace or custom-glyph
+/// boundary that would otherwise overflow it. Returns each line's shaped
+/// glyphs alongside its total advance.
+fn shape_lines(
+    faces: &[&owned_ttf_parser::Face],
+    items: &[MsdfTextItem],
+    wrap_width: Option<f32>,
+) -> Vec<(Vec<ShapedGlyph>, f32)> {
+    let mut lines = vec![];
+    let mut line: Vec<ShapedGlyph> = vec![];
+    let mut cursor = 0.0;
+    let mut last_glyph: Option<(usize, GlyphId)> = None;
+    let mut last_break: Option<(usize, f32)> = None;
+
+    macro_rules! break_line {
+        () => {{
+            lines.push((std::mem::take(&mut line), cursor));
+            cursor = 0.0;
+            last_glyph = None;
+            last_break = None;
+        }};
+    }
+
+    macro_rules! wrap_if_needed {
+        ($advance_end:expr) => {
+            if let Some((break_at, break_width)) = wrap_decision($advance_end, wrap_width, last_break) {
+                let rest = split_and_rebase(&mut line, break_at, break_width);
+                lines.push((line, break_width));
+                line = rest;
+                cursor -= break_width;
+                last_break = None;
+            }
+        };
+    }
+
+    for item in items {
+        match item {
+            MsdfTextItem::Text(text) => {
+                for paragraph in text.split('\n').enumerate() {
+                    let (index, paragraph) = paragraph;
+                    if index > 0 {
+                        break_line!();
+                    }
+
+                    for c in paragraph.chars() {
+                        let Some((face_index, glyph)) = resolve_glyph(faces, c) else {
+                            continue;
+                        };
+
+                        // Kerning only applies between two glyphs shaped
+                        // from the same face; a fallback face's metrics
+                        // have no relation to the primary face's.
+                        let kerning = last_glyph
+                            .filter(|(last_face, _)| *last_face == face_index)
+                            .map(|(_, last)| glyph_kerning(faces[face_index], last, glyph))
+                            .unwrap_or(0.0);
+
+                        let mut x = cursor + kerning;
+
+                        if c.is_whitespace() {
+                            wrap_if_needed!(x);
+                            // wrap_if_needed! may have rebased `cursor` onto
+                            // the continuation line; re-read it so `x` (and
+                            // everything derived from it below) reflects
+                            // that, same as the custom-glyph branch does.
+                            x = cursor + kerning;
+                        }
+
+                        let advance = glyph_advance(faces[face_index], glyph);
+                        line.push(ShapedGlyph {
+                            content: ShapedContent::Glyph { glyph, face_index },
+                            x,
+                        });
+
+                        cursor = x + advance;
+                        last_glyph = Some((face_index, glyph));
+
+                        if c.is_whitespace() {
+                            // Recorded after the space glyph is pushed, so
+                            // the break point falls right after it — the
+                            // space stays at the end of the completed line
+                            // rather than leading the continuation line.
+                            last_break = Some((line.len(), cursor));
+                        }
+                    }
+                }
+            }
+            MsdfTextItem::Custom(custom) => {
+                let x = cursor;
+                wrap_if_needed!(x + custom.size.x);
+                let x = cursor;
+
+                line.push(ShapedGlyph {
+                    content: ShapedContent::Custom(*custom),
+                    x,
+                });
+
+                cursor = x + custom.size.x;
+                last_glyph = None;
+                last_break = Some((line.len(), cursor));
+            }
+        }
+    }
+
+    lines.push((line, cursor));
+
+    // Re-derive each line's glyph positions relative to its own start, since
+    // a wrap may have split a line partway through accumulated `x` values.
+    for (line, _) in &mut lines {
+        if let Some(first) = line.first() {
+            let offset = first.x;
+            for glyph in line.iter_mut() {
+                glyph.x -= offset;
+            }
+        }
+    }
+
+    lines
 }
 
 pub fn layout(
     mut commands: Commands,
     mut atlas_events: EventReader<AssetEvent<MsdfAtlas>>,
-    texts: Query<(Entity, Ref<MsdfText>)>,
+    texts: Query<(Entity, Ref<MsdfText>, Option<Ref<MsdfFontStack>>)>,
     atlases: Res<Assets<MsdfAtlas>>,
 ) {
     let loaded_atlases = atlas_events
@@ -156,8 +641,17 @@ pub fn layout(
         })
         .collect::<HashSet<_>>();
 
-    for (entity, text) in texts.iter() {
-        if !text.is_changed() && !loaded_atlases.contains(&text.atlas.id()) {
+    for (entity, text, stack) in texts.iter() {
+        let stack_handles = stack.as_deref().map(|s| s.0.as_slice()).unwrap_or(&[]);
+
+        let relevant_atlas_loaded = loaded_atlases.contains(&text.atlas.id())
+            || stack_handles
+                .iter()
+                .any(|handle| loaded_atlases.contains(&handle.id()));
+
+        let stack_changed = stack.as_ref().is_some_and(|s| s.is_changed());
+
+        if !text.is_changed() && !stack_changed && !relevant_atlas_loaded {
             continue;
         }
 
@@ -165,30 +659,80 @@ pub fn layout(
             continue;
         };
 
+        // Fallback faces that failed to load are simply skipped, same as a
+        // character with no glyph in any face.
+        let fallback_atlases = stack_handles
+            .iter()
+            .filter_map(|handle| atlases.get(handle).map(|atlas| (handle.clone(), atlas)));
+
+        let mut draw_atlases = vec![text.atlas.clone()];
+        let mut faces = vec![atlas.face.as_face_ref()];
+
+        for (handle, fallback) in fallback_atlases {
+            draw_atlases.push(handle);
+            faces.push(fallback.face.as_face_ref());
+        }
+
+        let lines = shape_lines(&faces, &text.content, text.wrap_width);
+
+        let total_height = (lines.len().saturating_sub(1)) as f32 * text.line_height;
+        let top = match text.v_align {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Middle => total_height / 2.0,
+            VerticalAlign::Bottom => total_height,
+        };
+
         let mut draw = MsdfDraw {
-            atlas: text.atlas.clone(),
+            atlases: draw_atlases,
             glyphs: vec![],
         };
 
-        let mut cursor = 0.0;
-
-        for c in text.content.chars() {
-            if let Some(glyph) = atlas.face.as_face_ref().glyph_index(c) {
-                draw.glyphs.push(MsdfGlyph {
-                    pos: Vec2::new(cursor, 0.0),
-                    color: text.color,
-                    index: glyph.0,
-                });
-            }
-
-            cursor += 0.7;
+        let mut line_extents = vec![];
+
+        for (line_index, (glyphs, width)) in lines.into_iter().enumerate() {
+            let x_offset = match text.h_align {
+                HorizontalAlign::Left => 0.0,
+                HorizontalAlign::Center => -width / 2.0,
+                HorizontalAlign::Right => -width,
+            };
+
+            let y = top - line_index as f32 * text.line_height;
+
+            line_extents.push(MsdfLineExtent {
+                y,
+                start_x: x_offset,
+                end_x: x_offset + width,
+            });
+
+            draw.glyphs
+                .extend(glyphs.into_iter().map(|shaped| match shaped.content {
+                    ShapedContent::Glyph { glyph, face_index } => MsdfGlyph {
+                        pos: Vec2::new(shaped.x + x_offset, y),
+                        color: text.color,
+                        index: glyph.0,
+                        content: glyph_content(faces[face_index], glyph),
+                        atlas_index: face_index,
+                    },
+                    ShapedContent::Custom(custom) => {
+                        let mut pos = Vec2::new(shaped.x + x_offset, y) + custom.offset;
+                        if custom.snap_to_physical {
+                            pos = pos.round();
+                        }
+
+                        MsdfGlyph {
+                            pos,
+                            color: text.color,
+                            index: 0,
+                            content: GlyphContent::Custom(custom.id),
+                            atlas_index: 0,
+                        }
+                    }
+                }));
         }
 
-        draw.glyphs
-            .iter_mut()
-            .for_each(|glyph| glyph.pos.x -= cursor / 2.0);
-
-        commands.entity(entity).insert(draw);
+        commands
+            .entity(entity)
+            .insert((draw, MsdfLines(line_extents)));
     }
 }
 
@@ -198,7 +742,113 @@ impl Plugin for MsdfPlugin {
     fn build(&self, app: &mut App) {
         app.init_asset::<MsdfAtlas>()
             .init_asset_loader::<MsdfAtlasLoader>()
+            .init_resource::<CustomGlyphRegistry>()
             .add_plugins(render::MsdfRenderPlugin)
             .add_systems(PostUpdate, layout);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(x: f32) -> ShapedGlyph {
+        ShapedGlyph {
+            content: ShapedContent::Custom(CustomGlyph {
+                id: 0,
+                size: Vec2::ZERO,
+                offset: Vec2::ZERO,
+                snap_to_physical: false,
+            }),
+            x,
+        }
+    }
+
+    #[test]
+    fn no_wrap_width_never_wraps() {
+        assert_eq!(wrap_decision(1000.0, None, Some((3, 50.0))), None);
+    }
+
+    #[test]
+    fn under_wrap_width_does_not_wrap() {
+        assert_eq!(wrap_decision(80.0, Some(100.0), Some((3, 50.0))), None);
+    }
+
+    #[test]
+    fn over_wrap_width_with_no_break_point_does_not_wrap() {
+        // A run with no whitespace/custom-glyph boundary yet has nowhere to
+        // break, so it's left to overflow rather than wrapping mid-glyph.
+        assert_eq!(wrap_decision(150.0, Some(100.0), None), None);
+    }
+
+    #[test]
+    fn over_wrap_width_wraps_at_last_break() {
+        assert_eq!(
+            wrap_decision(150.0, Some(100.0), Some((3, 50.0))),
+            Some((3, 50.0))
+        );
+    }
+
+    #[test]
+    fn exactly_at_wrap_width_does_not_wrap() {
+        assert_eq!(wrap_decision(100.0, Some(100.0), Some((3, 50.0))), None);
+    }
+
+    #[test]
+    fn split_and_rebase_moves_the_remainder_into_the_new_frame() {
+        let mut line = vec![glyph(0.0), glyph(10.0), glyph(20.0), glyph(30.0)];
+        let rest = split_and_rebase(&mut line, 2, 20.0);
+
+        assert_eq!(line.iter().map(|g| g.x).collect::<Vec<_>>(), vec![0.0, 10.0]);
+        assert_eq!(rest.iter().map(|g| g.x).collect::<Vec<_>>(), vec![0.0, 10.0]);
+    }
+
+    #[test]
+    fn split_and_rebase_prevents_double_shift_after_a_wrap() {
+        // Regression test for a bug where a continuation line containing
+        // more than one wrap-worthy word got corrupted: carried-over
+        // glyphs kept their pre-wrap `x` while glyphs appended afterwards
+        // were already computed from the rebased cursor, so the two
+        // frames collided once a blanket end-of-line offset was applied.
+        //
+        // Simulates shaping "ab cdef gh" at a 10-unit advance per glyph
+        // with wrap_width = 25: reaching the space after "cdef" overflows,
+        // so the line wraps after "ab ", and the space, "g" and "h" are
+        // then appended to the continuation line.
+        let mut line = vec![
+            glyph(0.0),  // "a"
+            glyph(10.0), // "b"
+            glyph(20.0), // " "
+            glyph(30.0), // "c"
+            glyph(40.0), // "d"
+            glyph(50.0), // "e"
+            glyph(60.0), // "f"
+        ];
+
+        // Break at index 3, right after the space at index 2, whose advance
+        // ended at 30.
+        let rest = split_and_rebase(&mut line, 3, 30.0);
+        assert_eq!(line.iter().map(|g| g.x).collect::<Vec<_>>(), vec![0.0, 10.0, 20.0]);
+
+        // `rest` ("cdef") is already rebased into the continuation line's
+        // own frame...
+        assert_eq!(
+            rest.iter().map(|g| g.x).collect::<Vec<_>>(),
+            vec![0.0, 10.0, 20.0, 30.0]
+        );
+
+        // ...so glyphs appended afterwards using the already-decremented
+        // cursor (a trailing space, then "g" and "h") land in that same
+        // frame instead of doubling back on top of "e"/"f".
+        let mut line = rest;
+        line.push(glyph(40.0)); // " "
+        line.push(glyph(50.0)); // "g"
+        line.push(glyph(60.0)); // "h"
+
+        assert_eq!(
+            line.iter().map(|g| g.x).collect::<Vec<_>>(),
+            vec![0.0, 10.0, 20.0, 30.0, 40.0, 50.0, 60.0],
+            "g/h must not collide with e/f after the wrap"
+        );
+    }
+}
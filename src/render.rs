@@ -0,0 +1,329 @@
+//! Render-facing glue for [MsdfDraw](crate::MsdfDraw): turning laid-out
+//! glyph indices into resident atlas entries, ready for the GPU upload and
+//! draw call that consume [GlyphAtlas] and [ColorGlyphAtlas]. The draw call
+//! itself samples `msdf.wgsl`, which branches on each instance's content
+//! type to choose the mask or color atlas texture.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use owned_ttf_parser::{AsFaceRef, GlyphId};
+
+use crate::{
+    atlas::{ColorGlyphAtlas, GlyphAtlas, GlyphAtlasError},
+    CustomGlyphRegistry, CustomGlyphSource, DecorationLine, DecorationStyle, GlyphContent,
+    MsdfAtlas, MsdfDecoration, MsdfDraw, MsdfLines,
+};
+
+/// Both atlas textures backing a single [MsdfAtlas] asset: the 3-channel
+/// (RGB) MSDF mask atlas, and the RGBA color atlas for glyphs with embedded
+/// color data.
+#[derive(Default)]
+pub struct MsdfAtlasState {
+    pub mask: GlyphAtlas,
+    pub color: ColorGlyphAtlas,
+}
+
+/// Per-[MsdfAtlas] atlas state, keyed by the asset whose face it rasterizes
+/// glyphs from.
+///
+/// This lives alongside the main world's [MsdfDraw] components rather than
+/// in the render world: growing and re-rasterizing needs the
+/// [owned_ttf_parser::Face] that produced the glyph outlines in the first
+/// place, which main-world assets already own.
+#[derive(Resource, Default)]
+pub struct GlyphAtlases(HashMap<AssetId<MsdfAtlas>, MsdfAtlasState>);
+
+impl GlyphAtlases {
+    /// Returns the packed atlas state for `id`, if one has been prepared.
+    pub fn get(&self, id: AssetId<MsdfAtlas>) -> Option<&MsdfAtlasState> {
+        self.0.get(&id)
+    }
+}
+
+/// Resolves every glyph referenced by a frame's [MsdfDraw]s against the
+/// mask or color atlas (per [MsdfGlyph::content](crate::MsdfGlyph::content)),
+/// rasterizing and packing on first use and growing whichever atlas fills up.
+pub fn prepare_glyph_atlases(
+    mut atlases_state: ResMut<GlyphAtlases>,
+    atlases: Res<Assets<MsdfAtlas>>,
+    custom_glyphs: Res<CustomGlyphRegistry>,
+    draws: Query<&MsdfDraw>,
+) {
+    for draw in draws.iter() {
+        for glyph in &draw.glyphs {
+            let Some(handle) = draw.atlases.get(glyph.atlas_index) else {
+                warn!("glyph references atlas index {} out of range", glyph.atlas_index);
+                continue;
+            };
+
+            let Some(atlas) = atlases.get(handle) else {
+                continue;
+            };
+
+            let state = atlases_state.0.entry(handle.id()).or_default();
+            let face = atlas.face.as_face_ref();
+
+            match glyph.content {
+                GlyphContent::Mask => {
+                    let id = GlyphId(glyph.index);
+                    match state.mask.get_or_rasterize(face, id) {
+                        Ok(_) => {}
+                        Err(GlyphAtlasError::Full) => {
+                            state.mask.grow(face);
+                            // Growing doubles the texture, so retry once. A
+                            // glyph still too large for the grown atlas is
+                            // warned about here and tried again (growing the
+                            // atlas again) on the next frame.
+                            if let Err(err) = state.mask.get_or_rasterize(face, id) {
+                                warn!("mask atlas retry after grow still failed: {err}");
+                            }
+                        }
+                        // Already warned about the first time this glyph
+                        // failed (e.g. the space character, which has no
+                        // outline); don't retry or re-log it every frame.
+                        Err(GlyphAtlasError::PreviouslyFailed) => {}
+                        Err(err) => warn!("failed to rasterize mask glyph {id:?}: {err}"),
+                    }
+                }
+                GlyphContent::Color => {
+                    let id = GlyphId(glyph.index);
+                    match state.color.get_or_rasterize(face, id) {
+                        Ok(_) => {}
+                        Err(GlyphAtlasError::Full) => {
+                            state.color.grow(face);
+                            if let Err(err) = state.color.get_or_rasterize(face, id) {
+                                warn!("color atlas retry after grow still failed: {err}");
+                            }
+                        }
+                        Err(GlyphAtlasError::PreviouslyFailed) => {}
+                        Err(err) => warn!("failed to rasterize color glyph {id:?}: {err}"),
+                    }
+                }
+                GlyphContent::Custom(custom_id) => {
+                    let Some(source) = custom_glyphs.get(custom_id) else {
+                        warn!("custom glyph {custom_id} has no registered source");
+                        continue;
+                    };
+
+                    // Mirrors the Mask/Color grow-and-retry above: a custom
+                    // glyph that doesn't fit the first time shouldn't stay
+                    // permanently invisible just because it came from the
+                    // registry instead of the font.
+                    let result = match source {
+                        CustomGlyphSource::Color {
+                            pixels,
+                            width,
+                            height,
+                        } => match state.color.insert_custom(
+                            custom_id,
+                            pixels,
+                            *width,
+                            *height,
+                            (0.0, 0.0, 1.0, 1.0),
+                        ) {
+                            Ok(_) => Ok(()),
+                            Err(GlyphAtlasError::Full) => {
+                                state.color.grow(face);
+                                state
+                                    .color
+                                    .insert_custom(custom_id, pixels, *width, *height, (0.0, 0.0, 1.0, 1.0))
+                                    .map(|_| ())
+                            }
+                            Err(err) => Err(err),
+                        },
+                        CustomGlyphSource::Msdf {
+                            pixels,
+                            width,
+                            height,
+                        } => match state.mask.insert_custom(
+                            custom_id,
+                            pixels,
+                            *width,
+                            *height,
+                            (0.0, 0.0, 1.0, 1.0),
+                        ) {
+                            Ok(_) => Ok(()),
+                            Err(GlyphAtlasError::Full) => {
+                                state.mask.grow(face);
+                                state
+                                    .mask
+                                    .insert_custom(custom_id, pixels, *width, *height, (0.0, 0.0, 1.0, 1.0))
+                                    .map(|_| ())
+                            }
+                            Err(err) => Err(err),
+                        },
+                    };
+
+                    if let Err(err) = result {
+                        warn!("failed to upload custom glyph {custom_id}: {err}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Clears each atlas's per-frame in-use set so glyphs no longer drawn this
+/// frame become eligible for LRU eviction again.
+pub fn trim_glyph_atlases(mut atlases_state: ResMut<GlyphAtlases>) {
+    for state in atlases_state.0.values_mut() {
+        state.mask.trim();
+        state.color.trim();
+    }
+}
+
+/// A single extra instanced quad spanning a laid-out line's extent, drawn
+/// by the `decoration.wgsl` shader which procedurally patterns it according
+/// to [DecorationStyle].
+pub struct DecorationInstance {
+    /// The rule's left edge, in the same space as [MsdfGlyph](crate::MsdfGlyph)::pos.
+    pub start: Vec2,
+    /// The rule's right edge.
+    pub end: Vec2,
+    pub thickness: f32,
+    pub color: Color,
+    pub style: DecorationStyle,
+}
+
+/// The decoration quads produced for a single [MsdfDecoration], analogous
+/// to [MsdfDraw] but for underline/overline/strikethrough rules.
+#[derive(Component, Default)]
+pub struct MsdfDecorationDraw {
+    pub instances: Vec<DecorationInstance>,
+}
+
+/// The font's underline, strikeout, and overline metrics, scaled into
+/// layout units, used as defaults when a [DecorationLine] doesn't specify
+/// its own thickness or position.
+struct DecorationMetrics {
+    underline_position: f32,
+    underline_thickness: f32,
+    strikeout_position: f32,
+    strikeout_thickness: f32,
+    overline_position: f32,
+}
+
+fn decoration_metrics(face: &owned_ttf_parser::Face) -> DecorationMetrics {
+    let units_per_em = face.units_per_em() as f32;
+
+    let (underline_position, underline_thickness) = face
+        .underline_metrics()
+        .map(|m| (m.position as f32 / units_per_em, m.thickness as f32 / units_per_em))
+        .unwrap_or((-0.1, 0.05));
+
+    let (strikeout_position, strikeout_thickness) = face
+        .strikeout_metrics()
+        .map(|m| (m.position as f32 / units_per_em, m.thickness as f32 / units_per_em))
+        .unwrap_or((0.25, 0.05));
+
+    // The font doesn't expose a dedicated overline metric, so sit it at the
+    // ascender, the way browsers place `text-decoration: overline`.
+    let overline_position = face.ascender() as f32 / units_per_em;
+
+    DecorationMetrics {
+        underline_position,
+        underline_thickness,
+        strikeout_position,
+        strikeout_thickness,
+        overline_position,
+    }
+}
+
+/// Builds a [DecorationInstance] for `line` spanning `extent`, falling back
+/// to `default_position`/`default_thickness` when the line doesn't override
+/// them.
+fn decoration_instance(
+    line: &DecorationLine,
+    extent: &crate::MsdfLineExtent,
+    default_position: f32,
+    default_thickness: f32,
+) -> DecorationInstance {
+    let position = line.position.unwrap_or(default_position);
+    let thickness = line.thickness.unwrap_or(default_thickness);
+
+    DecorationInstance {
+        start: Vec2::new(extent.start_x, extent.y + position),
+        end: Vec2::new(extent.end_x, extent.y + position),
+        thickness,
+        color: line.color,
+        style: line.style,
+    }
+}
+
+/// Builds decoration quads for every [MsdfDecoration] from its [MsdfLines]
+/// geometry and its [MsdfAtlas]'s underline/strikeout metrics.
+///
+/// Only entities whose [MsdfDecoration] or [MsdfLines] changed this frame
+/// are rebuilt, the same as [crate::layout] gates on `Ref<MsdfText>` — the
+/// geometry is otherwise unchanged frame to frame for static text.
+pub fn prepare_decorations(
+    mut commands: Commands,
+    atlases: Res<Assets<MsdfAtlas>>,
+    decorations: Query<(Entity, Ref<MsdfDecoration>, Ref<MsdfLines>, &MsdfDraw)>,
+) {
+    for (entity, decoration, lines, draw) in decorations.iter() {
+        if !decoration.is_changed() && !lines.is_changed() {
+            continue;
+        }
+
+        // Decoration metrics come from the primary face only; fallback faces
+        // in a font stack don't get their own underline/strikeout rules.
+        let Some(primary) = draw.atlases.first() else {
+            continue;
+        };
+
+        let Some(atlas) = atlases.get(primary) else {
+            continue;
+        };
+
+        let metrics = decoration_metrics(atlas.face.as_face_ref());
+        let mut instances = vec![];
+
+        for extent in &lines.0 {
+            if let Some(underline) = &decoration.underline {
+                instances.push(decoration_instance(
+                    underline,
+                    extent,
+                    metrics.underline_position,
+                    metrics.underline_thickness,
+                ));
+            }
+
+            if let Some(strikethrough) = &decoration.strikethrough {
+                instances.push(decoration_instance(
+                    strikethrough,
+                    extent,
+                    metrics.strikeout_position,
+                    metrics.strikeout_thickness,
+                ));
+            }
+
+            if let Some(overline) = &decoration.overline {
+                instances.push(decoration_instance(
+                    overline,
+                    extent,
+                    metrics.overline_position,
+                    metrics.underline_thickness,
+                ));
+            }
+        }
+
+        commands.entity(entity).insert(MsdfDecorationDraw { instances });
+    }
+}
+
+pub struct MsdfRenderPlugin;
+
+impl Plugin for MsdfRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GlyphAtlases>().add_systems(
+            PostUpdate,
+            (
+                (prepare_glyph_atlases, trim_glyph_atlases).chain(),
+                prepare_decorations,
+            )
+                .after(crate::layout),
+        );
+    }
+}